@@ -17,12 +17,128 @@ struct Script {
 struct Cmd {
     name: String,
     bind: Vec<String>,
+    #[serde(deserialize_with = "deserialize_cmd")]
     cmd: Vec<String>,
     cwd: String,
+    on_failure: OnFailure,
+    capture: String,
+    pipe: bool,
+    stdin: String,
+    stdout: String,
+    append: String,
+}
+
+/// Accepts either the array form (`cmd = ["echo", "hi"]`, taken literally)
+/// or a single shell-words string (`cmd = "echo hi"`, quote/escape-aware
+/// tokenized).
+fn deserialize_cmd<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CmdField {
+        Array(Vec<String>),
+        String(String),
+    }
+
+    match CmdField::deserialize(deserializer)? {
+        CmdField::Array(args) => Ok(args),
+        CmdField::String(s) => split_shell_words(&s).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Splits a string into words the way a POSIX shell would: unquoted
+/// whitespace separates words, single quotes take everything literally,
+/// double quotes allow backslash-escaping of `" \ $ \``, and a bare
+/// backslash escapes the next character.
+fn split_shell_words(s: &str) -> std::result::Result<Vec<String>, String> {
+    #[derive(PartialEq)]
+    enum Quote {
+        None,
+        Single,
+        Double,
+    }
+
+    let mut words = vec![];
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = Quote::None;
+
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match quote {
+            Quote::Single => {
+                if c == '\'' {
+                    quote = Quote::None;
+                } else {
+                    current.push(c);
+                }
+            }
+            Quote::Double => match c {
+                '"' => quote = Quote::None,
+                '\\' => match chars.clone().next() {
+                    Some(next @ ('"' | '\\' | '$' | '`')) => {
+                        current.push(next);
+                        chars.next();
+                    }
+                    _ => current.push('\\'),
+                },
+                _ => current.push(c),
+            },
+            Quote::None => match c {
+                c if c.is_whitespace() => {
+                    if in_word {
+                        words.push(std::mem::take(&mut current));
+                        in_word = false;
+                    }
+                }
+                '\'' => {
+                    quote = Quote::Single;
+                    in_word = true;
+                }
+                '"' => {
+                    quote = Quote::Double;
+                    in_word = true;
+                }
+                '\\' => {
+                    let next = chars.next().ok_or("trailing backslash")?;
+                    current.push(next);
+                    in_word = true;
+                }
+                c => {
+                    current.push(c);
+                    in_word = true;
+                }
+            },
+        }
+    }
+
+    if quote != Quote::None {
+        return Err("unterminated quote".to_string());
+    }
+
+    if in_word {
+        words.push(current);
+    }
+
+    Ok(words)
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum OnFailure {
+    #[default]
+    Abort,
+    Ignore,
+    Warn,
 }
 
 struct Env {
     binds: HashMap<String, Vec<String>>,
+    /// Persistent working directory set by the `cd` builtin, applied to
+    /// every subsequently spawned command that doesn't set its own `cwd`.
+    cwd: Option<PathBuf>,
 }
 
 impl Env {
@@ -48,9 +164,35 @@ impl Env {
             return Ok(vec!["$".to_string()]);
         }
 
+        let name = Self::parse_name(peek)?;
+
+        // $env:NAME forces an OS environment lookup, bypassing script binds.
+        if name == "env" && peek.next_if_eq(&':').is_some() {
+            let var = Self::parse_name(peek)?;
+
+            return std::env::var(&var)
+                .map(|value| vec![value])
+                .map_err(|_| eprintln!("Missing bind for {var}"));
+        }
+
+        if let Some(bind) = self.binds.get(&name) {
+            return Ok(bind.to_owned());
+        }
+
+        // Script binds take precedence; fall back to the OS environment so
+        // scripts can read things like $HOME or CI-injected variables.
+        if let Ok(value) = std::env::var(&name) {
+            return Ok(vec![value]);
+        }
+
+        eprintln!("Missing bind for {name}");
+        Err(())
+    }
+
+    fn parse_name<I: Iterator<Item = char>>(peek: &mut Peekable<I>) -> Result<String> {
         let mut name = String::new();
 
-        while let Some(c) = peek.next_if(char::is_ascii_alphanumeric) {
+        while let Some(c) = peek.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
             name.push(c);
         }
 
@@ -58,12 +200,7 @@ impl Env {
             return Err(());
         }
 
-        let bind = self
-            .binds
-            .get(&name)
-            .ok_or_else(|| eprintln!("Missing bind for {name}"))?;
-
-        Ok(bind.to_owned())
+        Ok(name)
     }
 
     fn eval(&self, text: &str) -> Result<Vec<String>> {
@@ -113,6 +250,33 @@ impl Env {
     }
 }
 
+#[test]
+fn test_split_shell_words() {
+    assert_eq!(
+        split_shell_words("echo hello world").unwrap(),
+        vec!["echo", "hello", "world"]
+    );
+    assert_eq!(
+        split_shell_words("  echo   hi  ").unwrap(),
+        vec!["echo", "hi"]
+    );
+    assert_eq!(
+        split_shell_words("cc $b.o -o out").unwrap(),
+        vec!["cc", "$b.o", "-o", "out"]
+    );
+    assert_eq!(
+        split_shell_words("echo 'a b' c").unwrap(),
+        vec!["echo", "a b", "c"]
+    );
+    assert_eq!(
+        split_shell_words(r#"echo "a \"b\"" c"#).unwrap(),
+        vec!["echo", "a \"b\"", "c"]
+    );
+    assert_eq!(split_shell_words("").unwrap(), Vec::<String>::new());
+    assert!(split_shell_words("echo \"unterminated").is_err());
+    assert!(split_shell_words("echo trailing\\").is_err());
+}
+
 #[test]
 fn test_eval() {
     let env = Env {
@@ -122,6 +286,7 @@ fn test_eval() {
         ]
         .into_iter()
         .collect(),
+        cwd: None,
     };
 
     assert_eq!(env.eval("$a").unwrap(), Vec::<String>::new());
@@ -142,49 +307,287 @@ fn test_eval() {
     );
 }
 
+#[test]
+fn test_env_interpolation() {
+    // SAFETY: this test doesn't spawn threads, and no other test reads these names.
+    unsafe {
+        std::env::set_var("TOMLSH_TEST_VAR", "value");
+        std::env::set_var("TOMLSH_TEST_EMPTY", "");
+        std::env::remove_var("TOMLSH_TEST_MISSING");
+    }
+
+    let env = Env {
+        binds: [("b".to_string(), vec!["bound".to_string()])]
+            .into_iter()
+            .collect(),
+        cwd: None,
+    };
+
+    // Bare $NAME falls through to the OS environment when there's no bind.
+    assert_eq!(env.eval("$TOMLSH_TEST_VAR").unwrap(), vec!["value"]);
+    // Script binds still win over the OS environment for the same name.
+    assert_eq!(env.eval("$b").unwrap(), vec!["bound"]);
+    // $env:NAME forces the OS lookup, and an empty value is not a miss.
+    assert_eq!(
+        env.eval("$env:TOMLSH_TEST_EMPTY").unwrap(),
+        vec!["".to_string()]
+    );
+    assert!(env.eval("$env:TOMLSH_TEST_MISSING").is_err());
+    assert!(env.eval("$TOMLSH_TEST_MISSING").is_err());
+}
+
 impl Script {
     fn run(&self, env: &mut Env) -> Result<()> {
-        for cmd in &self.cmd {
-            if !cmd.name.is_empty() {
-                env.binds.insert(cmd.name.to_owned(), cmd.bind.clone());
+        let mut i = 0;
+        while i < self.cmd.len() {
+            let step = &self.cmd[i];
+
+            if !step.name.is_empty() {
+                env.binds.insert(step.name.to_owned(), step.bind.clone());
             }
 
-            if !cmd.cmd.is_empty() {
-                let cmd = &cmd.cmd;
+            if step.cmd.is_empty() {
+                i += 1;
+                continue;
+            }
 
-                let mut cmd_eval = vec![];
-                for arg in cmd {
-                    cmd_eval.extend(env.eval(arg)?);
+            let mut end = i;
+            while self.cmd[end].pipe && end + 1 < self.cmd.len() {
+                end += 1;
+                if !self.cmd[end].name.is_empty() {
+                    env.binds
+                        .insert(self.cmd[end].name.to_owned(), self.cmd[end].bind.clone());
                 }
+            }
 
-                if cmd_eval.is_empty() {
-                    eprintln!("Empty command: {:?}", cmd);
+            self.run_pipeline(env, &self.cmd[i..=end])?;
+
+            i = end + 1;
+        }
+
+        Ok(())
+    }
+
+    /// Spawns one or more commands chained together by `pipe = true`, wiring
+    /// each stage's stdout into the next stage's stdin. Only the first stage
+    /// may redirect stdin from a file, and only the last stage may redirect
+    /// stdout to a file or capture it into a bind; intermediate stages are
+    /// always piped.
+    fn run_pipeline(&self, env: &mut Env, stages: &[Cmd]) -> Result<()> {
+        let mut children = vec![];
+        let mut prev_stdout = None;
+
+        for (idx, step) in stages.iter().enumerate() {
+            let is_last = idx + 1 == stages.len();
+
+            let mut cmd_eval = vec![];
+            for arg in &step.cmd {
+                cmd_eval.extend(env.eval(arg)?);
+            }
+
+            if cmd_eval.is_empty() {
+                eprintln!("Empty command: {:?}", step.cmd);
+                return Err(());
+            }
+
+            if is_builtin(&cmd_eval[0]) {
+                if !is_last
+                    || step.pipe
+                    || prev_stdout.is_some()
+                    || !step.cwd.is_empty()
+                    || !step.stdin.is_empty()
+                    || !step.stdout.is_empty()
+                    || !step.append.is_empty()
+                    || !step.capture.is_empty()
+                {
+                    eprintln!(
+                        "{}: builtins can't be piped, redirected, captured, or given a per-step cwd",
+                        cmd_eval[0]
+                    );
                     return Err(());
                 }
 
-                let mut cmd = std::process::Command::new(&cmd_eval[0]);
+                run_builtin(env, &cmd_eval).expect("is_builtin confirmed this is a builtin")?;
+                continue;
+            }
 
-                cmd.args(&cmd_eval[1..]);
+            let mut cmd = std::process::Command::new(&cmd_eval[0]);
+            cmd.args(&cmd_eval[1..]);
 
-                if self.verbose {
-                    eprintln!("tomlsh: => {:?}", cmd);
-                }
+            let cwd = if !step.cwd.is_empty() {
+                Some(PathBuf::from(eval_one(env, &step.cwd)?))
+            } else {
+                env.cwd.clone()
+            };
+            if let Some(cwd) = cwd {
+                cmd.current_dir(cwd);
+            }
 
-                let status = cmd
-                    .status()
-                    .map_err(|err| eprintln!("Failed start command {}: {err}", cmd_eval[0]))?;
+            if let Some(stdout) = prev_stdout.take() {
+                cmd.stdin(std::process::Stdio::from(stdout));
+            } else if !step.stdin.is_empty() {
+                let path = eval_one(env, &step.stdin)?;
+                let file = std::fs::File::open(&path)
+                    .map_err(|err| eprintln!("Failed to open {path} for reading: {err}"))?;
+                cmd.stdin(std::process::Stdio::from(file));
+            }
 
-                if !status.success() {
-                    eprintln!("Command {} failed with {:?}", cmd_eval[0], status.code());
-                    return Err(());
+            let capturing = is_last && !step.capture.is_empty();
+
+            if !is_last || capturing {
+                cmd.stdout(std::process::Stdio::piped());
+            } else if !step.stdout.is_empty() {
+                let path = eval_one(env, &step.stdout)?;
+                let file = std::fs::File::create(&path)
+                    .map_err(|err| eprintln!("Failed to open {path} for writing: {err}"))?;
+                cmd.stdout(std::process::Stdio::from(file));
+            } else if !step.append.is_empty() {
+                let path = eval_one(env, &step.append)?;
+                let file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .map_err(|err| eprintln!("Failed to open {path} for appending: {err}"))?;
+                cmd.stdout(std::process::Stdio::from(file));
+            }
+
+            if self.verbose {
+                eprintln!("tomlsh: => {:?}", cmd);
+            }
+
+            let mut child = cmd
+                .spawn()
+                .map_err(|err| eprintln!("Failed start command {}: {err}", cmd_eval[0]))?;
+
+            if !is_last {
+                prev_stdout = child.stdout.take();
+            } else if capturing {
+                use std::io::Read;
+
+                let mut stdout = String::new();
+                child
+                    .stdout
+                    .take()
+                    .expect("last stage's stdout was piped for capture")
+                    .read_to_string(&mut stdout)
+                    .map_err(|err| eprintln!("Failed to read output of {}: {err}", cmd_eval[0]))?;
+
+                let mut lines: Vec<String> = stdout.split('\n').map(str::to_owned).collect();
+                if lines.last().is_some_and(String::is_empty) {
+                    lines.pop();
                 }
+                env.binds.insert(step.capture.to_owned(), lines);
             }
+
+            children.push((child, &step.on_failure, cmd_eval[0].clone()));
+        }
+
+        let mut results = vec![];
+        for (mut child, on_failure, program) in children {
+            let status = child
+                .wait()
+                .map_err(|err| eprintln!("Failed to wait for {program}: {err}"))?;
+
+            results.push((on_failure, program, status));
+        }
+
+        for (on_failure, program, status) in results {
+            handle_status(on_failure, &program, status)?;
         }
 
         Ok(())
     }
 }
 
+/// Evaluates `text` and requires it expand to exactly one value, as needed
+/// for fields like `cwd`, `stdin`, `stdout` and `append` that name a single
+/// path rather than a command's argument list.
+fn eval_one(env: &Env, text: &str) -> Result<String> {
+    let mut values = env.eval(text)?;
+
+    if values.len() != 1 {
+        eprintln!("Expected {text:?} to expand to exactly one value, got {values:?}");
+        return Err(());
+    }
+
+    Ok(values.remove(0))
+}
+
+/// Whether `name` is one of the in-process builtins. Builtins never spawn a
+/// process, so they can't be wired into `stdin`/`stdout`/`append`/`capture`
+/// or a `pipe` chain; callers must check this before those apply.
+fn is_builtin(name: &str) -> bool {
+    matches!(name, "cd" | "export" | "set" | "echo")
+}
+
+/// Dispatches in-process shell builtins that need to affect tomlsh's own
+/// state (`cd`, `export`/`set`, `echo`) instead of forking a process.
+/// Returns `None` when `args[0]` isn't a builtin, so the caller falls
+/// through to spawning it as an external command.
+fn run_builtin(env: &mut Env, args: &[String]) -> Option<Result<()>> {
+    match args[0].as_str() {
+        "cd" => Some(builtin_cd(env, args)),
+        "export" | "set" => Some(builtin_export(env, args)),
+        "echo" => Some(builtin_echo(args)),
+        _ => None,
+    }
+}
+
+fn builtin_cd(env: &mut Env, args: &[String]) -> Result<()> {
+    let Some(dir) = args.get(1) else {
+        eprintln!("cd: missing directory");
+        return Err(());
+    };
+
+    let base = match &env.cwd {
+        Some(cwd) => cwd.clone(),
+        None => std::env::current_dir().map_err(|err| eprintln!("cd: {err}"))?,
+    };
+
+    env.cwd = Some(base.join(dir));
+
+    Ok(())
+}
+
+fn builtin_export(env: &mut Env, args: &[String]) -> Result<()> {
+    let Some(name) = args.get(1) else {
+        eprintln!("export: missing name");
+        return Err(());
+    };
+
+    env.binds.insert(name.to_owned(), args[2..].to_vec());
+
+    Ok(())
+}
+
+fn builtin_echo(args: &[String]) -> Result<()> {
+    println!("{}", args[1..].join(" "));
+
+    Ok(())
+}
+
+fn handle_status(
+    policy: &OnFailure,
+    program: &str,
+    status: std::process::ExitStatus,
+) -> Result<()> {
+    if status.success() {
+        return Ok(());
+    }
+
+    match policy {
+        OnFailure::Abort => {
+            eprintln!("Command {program} failed with {:?}", status.code());
+            Err(())
+        }
+        OnFailure::Warn => {
+            eprintln!("Command {program} failed with {:?}", status.code());
+            Ok(())
+        }
+        OnFailure::Ignore => Ok(()),
+    }
+}
+
 #[derive(Parser)]
 struct CommandLine {
     /// A .toml file contains script of tomlsh.
@@ -193,6 +596,12 @@ struct CommandLine {
     /// Overwrite .verbose of script
     #[clap(long)]
     verbose: bool,
+
+    /// Seed a bind before running, as NAME=VALUE (NAME=v1,v2 for multiple
+    /// values). Repeatable. Visible to the script's first command; a later
+    /// `cmd.name`/`cmd.bind` for the same NAME overwrites it.
+    #[clap(long = "param", value_name = "NAME=VALUE")]
+    params: Vec<String>,
 }
 
 fn main() -> Result<()> {
@@ -210,8 +619,20 @@ fn main() -> Result<()> {
 
     let mut env = Env {
         binds: HashMap::new(),
+        cwd: None,
     };
 
+    for param in &cli.params {
+        let (name, value) = param
+            .split_once('=')
+            .ok_or_else(|| eprintln!("Invalid --param {param:?}, expected NAME=VALUE"))?;
+
+        env.binds.insert(
+            name.to_owned(),
+            value.split(',').map(str::to_owned).collect(),
+        );
+    }
+
     script.run(&mut env)?;
 
     Ok(())